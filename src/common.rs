@@ -0,0 +1,481 @@
+//! Processing-agnostic pieces shared by the pyo3 extension (`lib.rs`) and the CLI
+//! binary (`main.rs`): compression, record writing, thread-count/order handling,
+//! k-mer/content filtering, header matching, and N50/L50 stats.
+//!
+//! `lib.rs` includes this as `mod common;`; `main.rs` includes the same file as
+//! `#[path = "common.rs"] mod common;` so both binary targets compile it on their
+//! own, without either depending on the other (the pyo3 `cdylib` target can't be
+//! linked into a plain binary).
+
+// niffler::send::get_writer takes its own send::compression::Format (distinct
+// from the non-Send niffler::Format re-exported at the crate root); Level is shared.
+use niffler::send::compression::Format;
+use niffler::Level;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Pick a compression format from the output path's extension.
+/// Unrecognized or missing extensions mean uncompressed output.
+pub fn compression_format_for_path(path: &Path) -> Format {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Format::Gzip,
+        Some("bz2") => Format::Bzip,
+        Some("zst") => Format::Zstd,
+        Some("xz") => Format::Lzma,
+        _ => Format::No,
+    }
+}
+
+/// Map a 1-9 compression level to niffler's `Level`, clamping out-of-range values.
+pub fn compression_level(level: u8) -> Level {
+    match level {
+        0 | 1 => Level::One,
+        2 => Level::Two,
+        3 => Level::Three,
+        4 => Level::Four,
+        5 => Level::Five,
+        6 => Level::Six,
+        7 => Level::Seven,
+        8 => Level::Eight,
+        _ => Level::Nine,
+    }
+}
+
+/// Wrap `output` in a compressing writer matching `path`'s extension (`.gz`, `.bz2`,
+/// `.zst`, `.xz`), or pass it through unchanged for any other extension.
+pub fn compressed_writer(
+    output: Box<dyn Write + Send>,
+    path: &Path,
+    level: u8,
+) -> std::io::Result<Box<dyn Write + Send>> {
+    niffler::send::get_writer(output, compression_format_for_path(path), compression_level(level))
+        .map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+/// Write a single record as FASTQ (if `qual` is present) or FASTA, wrapping
+/// FASTA sequence lines at `line_width` characters (0 = single line).
+pub fn write_record(
+    writer: &mut impl Write,
+    id: &str,
+    desc: Option<&[u8]>,
+    seq: &str,
+    qual: Option<&str>,
+    line_width: usize,
+) -> std::io::Result<()> {
+    let desc = desc.filter(|d| !d.is_empty()).map(String::from_utf8_lossy);
+
+    if let Some(qual) = qual {
+        match &desc {
+            Some(desc) => writeln!(writer, "@{} {}", id, desc)?,
+            None => writeln!(writer, "@{}", id)?,
+        }
+        writeln!(writer, "{}", seq)?;
+        writeln!(writer, "+")?;
+        writeln!(writer, "{}", qual)?;
+    } else {
+        match &desc {
+            Some(desc) => writeln!(writer, ">{} {}", id, desc)?,
+            None => writeln!(writer, ">{}", id)?,
+        }
+        if line_width == 0 {
+            writeln!(writer, "{}", seq)?;
+        } else {
+            for chunk in seq.as_bytes().chunks(line_width) {
+                writeln!(writer, "{}", std::str::from_utf8(chunk).unwrap_or_default())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// paraseq gives user processors no global per-record position, so preserving
+/// input order means giving up parallelism rather than reordering output after
+/// the fact (same approach `parse_records` already uses).
+pub fn effective_num_threads(requested: usize, preserve_order: bool) -> usize {
+    if preserve_order {
+        1
+    } else {
+        requested
+    }
+}
+
+/// Split a FASTA/FASTQ header (paraseq's `Record::id()` returns the whole header
+/// line, ID token and description unsplit) into the ID token and the remaining
+/// description, on the first whitespace byte.
+pub fn split_id_desc(header: &[u8]) -> (&[u8], Option<&[u8]>) {
+    match header.iter().position(|b| b.is_ascii_whitespace()) {
+        Some(pos) => (&header[..pos], Some(&header[pos + 1..])),
+        None => (header, None),
+    }
+}
+
+/// Encode A/C/G/T as 2 bits; any other base breaks the current k-mer window.
+fn encode_base(base: u8) -> Option<u64> {
+    match base {
+        b'A' | b'a' => Some(0b00),
+        b'C' | b'c' => Some(0b01),
+        b'G' | b'g' => Some(0b10),
+        b'T' | b't' => Some(0b11),
+        _ => None,
+    }
+}
+
+/// Reverse-complement a 2-bit-packed k-mer.
+fn revcomp_kmer(kmer: u64, k: usize) -> u64 {
+    let mut rc = 0u64;
+    let mut kmer = kmer;
+    for _ in 0..k {
+        rc = (rc << 2) | (0b11 - (kmer & 0b11));
+        kmer >>= 2;
+    }
+    rc
+}
+
+/// The lexicographically smaller of a k-mer and its reverse complement.
+fn canonical_kmer(kmer: u64, k: usize) -> u64 {
+    kmer.min(revcomp_kmer(kmer, k))
+}
+
+/// Slide a k-window over `seq`, calling `f` with each canonical, ACGT-only k-mer.
+/// Windows containing any non-ACGT base are skipped.
+fn for_each_canonical_kmer(seq: &[u8], k: usize, mut f: impl FnMut(u64)) {
+    if k == 0 || k > 32 || seq.len() < k {
+        return;
+    }
+    let mask = if k == 32 { u64::MAX } else { (1u64 << (2 * k)) - 1 };
+    let mut kmer: u64 = 0;
+    let mut valid = 0usize;
+    for &base in seq {
+        match encode_base(base) {
+            Some(code) => {
+                kmer = ((kmer << 2) | code) & mask;
+                valid += 1;
+                if valid >= k {
+                    f(canonical_kmer(kmer, k));
+                }
+            }
+            None => {
+                kmer = 0;
+                valid = 0;
+            }
+        }
+    }
+}
+
+/// K-mers are canonicalized as 2-bit-packed `u64`s, which only holds up to 32 bases.
+/// `k` outside 1..=32 silently builds an empty k-mer set (every window gets skipped),
+/// which then makes `ContentFilter::matches` reject every record with no error at all.
+pub fn validate_k(k: usize) -> Result<(), String> {
+    if (1..=32).contains(&k) {
+        Ok(())
+    } else {
+        Err(format!("k must be between 1 and 32 (got {})", k))
+    }
+}
+
+/// Build the canonical k-mer set from a list of probe sequences.
+pub fn build_kmer_set(probes: &[String], k: usize) -> HashSet<u64> {
+    let mut set = HashSet::new();
+    for probe in probes {
+        for_each_canonical_kmer(probe.as_bytes(), k, |kmer| {
+            set.insert(kmer);
+        });
+    }
+    set
+}
+
+/// Whether `seq` contains any window whose canonical k-mer is in `kmers`.
+fn seq_has_kmer(seq: &[u8], k: usize, kmers: &HashSet<u64>) -> bool {
+    let mut found = false;
+    for_each_canonical_kmer(seq, k, |kmer| {
+        found = found || kmers.contains(&kmer);
+    });
+    found
+}
+
+/// Fraction of `seq` that is G or C.
+fn gc_fraction(seq: &[u8]) -> f64 {
+    if seq.is_empty() {
+        return 0.0;
+    }
+    let gc = seq
+        .iter()
+        .filter(|b| matches!(b, b'G' | b'g' | b'C' | b'c'))
+        .count();
+    gc as f64 / seq.len() as f64
+}
+
+/// Sequence-content predicates, ANDed together and with the header filter.
+/// A field left at its default (`None`) imposes no constraint, so a default
+/// `ContentFilter` matches every record. A k-mer set that is present but empty
+/// matches no window, so it rejects every record.
+#[derive(Clone, Default)]
+pub struct ContentFilter {
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    pub min_gc: Option<f64>,
+    pub max_gc: Option<f64>,
+    pub kmers: Option<Arc<HashSet<u64>>>,
+    pub k: usize,
+}
+
+impl ContentFilter {
+    pub fn matches(&self, seq: &[u8]) -> bool {
+        if let Some(min_len) = self.min_len {
+            if seq.len() < min_len {
+                return false;
+            }
+        }
+        if let Some(max_len) = self.max_len {
+            if seq.len() > max_len {
+                return false;
+            }
+        }
+        if self.min_gc.is_some() || self.max_gc.is_some() {
+            let gc = gc_fraction(seq);
+            if self.min_gc.is_some_and(|min_gc| gc < min_gc) {
+                return false;
+            }
+            if self.max_gc.is_some_and(|max_gc| gc > max_gc) {
+                return false;
+            }
+        }
+        if let Some(kmers) = &self.kmers {
+            if !seq_has_kmer(seq, self.k, kmers) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// How header patterns select records. An empty pattern list always matches,
+/// so header filtering can be left off entirely (e.g. to filter by content only).
+pub enum HeaderMatcher {
+    Exact(HashSet<String>),
+    Substring(Vec<String>),
+    Glob(Vec<glob::Pattern>),
+    Regex(Vec<regex::Regex>),
+}
+
+impl HeaderMatcher {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            HeaderMatcher::Exact(set) => set.is_empty(),
+            HeaderMatcher::Substring(patterns) => patterns.is_empty(),
+            HeaderMatcher::Glob(patterns) => patterns.is_empty(),
+            HeaderMatcher::Regex(patterns) => patterns.is_empty(),
+        }
+    }
+
+    pub fn matches(&self, id: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        match self {
+            HeaderMatcher::Exact(set) => set.contains(id),
+            HeaderMatcher::Substring(patterns) => patterns.iter().any(|p| id.contains(p.as_str())),
+            HeaderMatcher::Glob(patterns) => patterns.iter().any(|p| p.matches(id)),
+            HeaderMatcher::Regex(patterns) => patterns.iter().any(|p| p.is_match(id)),
+        }
+    }
+}
+
+/// Compile header patterns into a matcher for `match_mode`
+/// ("exact", "substring", "glob", or "regex").
+pub fn compile_header_matcher(headers: Vec<String>, match_mode: &str) -> Result<HeaderMatcher, String> {
+    match match_mode {
+        "exact" => Ok(HeaderMatcher::Exact(headers.into_iter().collect())),
+        "substring" => Ok(HeaderMatcher::Substring(headers)),
+        "glob" => headers
+            .iter()
+            .map(|p| glob::Pattern::new(p).map_err(|e| format!("Invalid glob pattern '{}': {}", p, e)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(HeaderMatcher::Glob),
+        "regex" => headers
+            .iter()
+            .map(|p| regex::Regex::new(p).map_err(|e| format!("Invalid regex pattern '{}': {}", p, e)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(HeaderMatcher::Regex),
+        other => Err(format!(
+            "Unknown match_mode '{}': expected exact, substring, glob, or regex",
+            other
+        )),
+    }
+}
+
+/// Number of Phred quality bins tracked in the quality histogram (covers Phred+33
+/// scores 0-93, which spans the sane range for Illumina/Nanopore/PacBio output).
+pub const QUAL_HISTOGRAM_BINS: usize = 94;
+
+/// Sort `lengths` descending and walk them to find N50 (the length at which the
+/// running sum of bases first reaches half of `total_bases`) and L50 (how many
+/// sequences were summed to get there).
+pub fn n50_l50(mut lengths: Vec<u64>, total_bases: u64) -> (u64, u64, Vec<u64>) {
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let half = total_bases.div_ceil(2);
+    let mut running = 0u64;
+    let mut n50 = 0u64;
+    let mut l50 = 0u64;
+    for &len in &lengths {
+        running += len;
+        l50 += 1;
+        if running >= half {
+            n50 = len;
+            break;
+        }
+    }
+    (n50, l50, lengths)
+}
+
+/// Min/max/mean/median sequence length. `sorted_lengths` must already be sorted
+/// (ascending or descending) for the median, which reads the middle element(s);
+/// min and max are taken over the whole slice.
+/// Returns all-zero if `sorted_lengths` is empty.
+pub fn length_stats(sorted_lengths: &[u64], total_bases: u64, num_seqs: u64) -> (u64, u64, f64, f64) {
+    if sorted_lengths.is_empty() {
+        return (0, 0, 0.0, 0.0);
+    }
+    let min_len = *sorted_lengths.iter().min().unwrap();
+    let max_len = *sorted_lengths.iter().max().unwrap();
+    let mean_len = total_bases as f64 / num_seqs as f64;
+    let mid = sorted_lengths.len() / 2;
+    let median_len = if sorted_lengths.len().is_multiple_of(2) {
+        (sorted_lengths[mid - 1] + sorted_lengths[mid]) as f64 / 2.0
+    } else {
+        sorted_lengths[mid] as f64
+    };
+    (min_len, max_len, mean_len, median_len)
+}
+
+/// Mean Phred quality score from a histogram indexed by score (see `QUAL_HISTOGRAM_BINS`).
+pub fn mean_qual(qual_hist: &[u64]) -> f64 {
+    let total_qual_bases: u64 = qual_hist.iter().sum();
+    if total_qual_bases == 0 {
+        return 0.0;
+    }
+    qual_hist
+        .iter()
+        .enumerate()
+        .map(|(score, &count)| score as f64 * count as f64)
+        .sum::<f64>()
+        / total_qual_bases as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revcomp_kmer_round_trips() {
+        // ACGT, 2 bits/base: A=00 C=01 G=10 T=11 -> 0b00_01_10_11
+        let acgt = 0b00_01_10_11u64;
+        let rc = revcomp_kmer(acgt, 4);
+        // revcomp(ACGT) is ACGT
+        assert_eq!(rc, acgt);
+        assert_eq!(revcomp_kmer(rc, 4), acgt);
+    }
+
+    #[test]
+    fn canonical_kmer_picks_the_smaller_of_a_kmer_and_its_revcomp() {
+        let kmer = 0b11_00_00_00u64; // TAAA
+        let rc = revcomp_kmer(kmer, 4); // TTTA
+        assert_eq!(canonical_kmer(kmer, 4), kmer.min(rc));
+    }
+
+    #[test]
+    fn for_each_canonical_kmer_skips_windows_with_non_acgt_bases() {
+        let mut seen = Vec::new();
+        for_each_canonical_kmer(b"ACNGT", 2, |kmer| seen.push(kmer));
+        // "AC" survives before the N; "NG" and the window spanning it are skipped,
+        // leaving "GT" as the only other full 2-mer.
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn content_filter_default_matches_everything() {
+        let filter = ContentFilter::default();
+        assert!(filter.matches(b"ACGT"));
+        assert!(filter.matches(b""));
+    }
+
+    #[test]
+    fn content_filter_enforces_length_and_gc_bounds() {
+        let filter = ContentFilter {
+            min_len: Some(4),
+            max_len: Some(6),
+            min_gc: Some(0.5),
+            ..ContentFilter::default()
+        };
+        assert!(!filter.matches(b"AC")); // too short
+        assert!(!filter.matches(b"ACGTACGT")); // too long
+        assert!(!filter.matches(b"AAAA")); // GC too low
+        assert!(filter.matches(b"ACGT")); // within bounds, GC = 0.5
+    }
+
+    #[test]
+    fn header_matcher_exact() {
+        let m = compile_header_matcher(vec!["seq1".to_string()], "exact").unwrap();
+        assert!(m.matches("seq1"));
+        assert!(!m.matches("seq10"));
+    }
+
+    #[test]
+    fn header_matcher_substring() {
+        let m = compile_header_matcher(vec!["seq".to_string()], "substring").unwrap();
+        assert!(m.matches("my_seq_1"));
+        assert!(!m.matches("other"));
+    }
+
+    #[test]
+    fn header_matcher_glob() {
+        let m = compile_header_matcher(vec!["seq*".to_string()], "glob").unwrap();
+        assert!(m.matches("seq123"));
+        assert!(!m.matches("other123"));
+    }
+
+    #[test]
+    fn header_matcher_regex() {
+        let m = compile_header_matcher(vec![r"^seq\d+$".to_string()], "regex").unwrap();
+        assert!(m.matches("seq123"));
+        assert!(!m.matches("seqabc"));
+    }
+
+    #[test]
+    fn header_matcher_empty_pattern_list_always_matches() {
+        let m = compile_header_matcher(Vec::new(), "exact").unwrap();
+        assert!(m.is_empty());
+        assert!(m.matches("anything"));
+    }
+
+    #[test]
+    fn compile_header_matcher_rejects_unknown_mode() {
+        assert!(compile_header_matcher(Vec::new(), "bogus").is_err());
+    }
+
+    #[test]
+    fn n50_l50_matches_hand_computed_example() {
+        // lengths 2,3,4,5,6 -> total_bases 20, half = 10.
+        // Sorted descending: 6,5,4,3,2. Running sum reaches 10 at 6+5=11, after 2 sequences.
+        let (n50, l50, sorted) = n50_l50(vec![2, 3, 4, 5, 6], 20);
+        assert_eq!(n50, 5);
+        assert_eq!(l50, 2);
+        assert_eq!(sorted, vec![6, 5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn content_filter_enforces_kmer_presence() {
+        let probes = vec!["ACGT".to_string()];
+        let kmers = Arc::new(build_kmer_set(&probes, 4));
+        let filter = ContentFilter {
+            kmers: Some(kmers),
+            k: 4,
+            ..ContentFilter::default()
+        };
+        assert!(filter.matches(b"TTACGTTT"));
+        assert!(!filter.matches(b"TTTTTTTT"));
+    }
+}
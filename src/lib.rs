@@ -1,27 +1,46 @@
 use pyo3::prelude::*;
 use pyo3::exceptions::PyRuntimeError;
 use paraseq::prelude::*;
-use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::{Arc, Mutex};
 
+/// An (id, sequence, quality) record tuple as handed back to Python; `quality` is
+/// `None` for FASTA input.
+type RecordTuple = (String, String, Option<String>);
+
+mod common;
+use common::{
+    build_kmer_set, compile_header_matcher, compressed_writer, effective_num_threads, length_stats,
+    mean_qual, n50_l50, split_id_desc, validate_k, write_record, ContentFilter, HeaderMatcher,
+    QUAL_HISTOGRAM_BINS,
+};
+
 #[derive(Clone)]
 struct FastaFilterPy {
-    headers: Arc<HashSet<String>>,
-    writer: Arc<Mutex<BufWriter<File>>>,
+    headers: Arc<HeaderMatcher>,
+    writer: Arc<Mutex<BufWriter<Box<dyn Write + Send>>>>,
     invert: bool,
+    line_width: usize,
+    content: ContentFilter,
     processed: Arc<Mutex<u64>>,
     written: Arc<Mutex<u64>>,
 }
 
 impl<R: Record> ParallelProcessor<R> for FastaFilterPy {
     fn process_record(&mut self, record: R) -> Result<(), paraseq::ProcessError> {
-        let id = record.id_str().to_string();
+        let (id_bytes, desc) = split_id_desc(record.id());
+        let id = String::from_utf8_lossy(id_bytes).into_owned();
         let seq_bytes = record.seq();
         let seq = std::str::from_utf8(&seq_bytes)
             .map_err(|e| paraseq::ProcessError::Process(Box::new(e)))?;
+        let qual_bytes = record.qual();
+        let qual = qual_bytes
+            .map(|q| std::str::from_utf8(q))
+            .transpose()
+            .map_err(|e| paraseq::ProcessError::Process(Box::new(e)))?;
 
         // Update processed count
         {
@@ -29,17 +48,21 @@ impl<R: Record> ParallelProcessor<R> for FastaFilterPy {
             *count += 1;
         }
 
-        let should_write = if self.invert {
-            !self.headers.contains(&id)
+        let header_match = if self.headers.is_empty() {
+            true
+        } else if self.invert {
+            !self.headers.matches(&id)
         } else {
-            self.headers.contains(&id)
+            self.headers.matches(&id)
         };
 
+        let should_write = header_match && self.content.matches(&seq_bytes);
+
         if should_write {
             let mut writer = self.writer.lock().unwrap();
-            writeln!(writer, ">{}\n{}", id, seq)
-                .map_err(|e| paraseq::ProcessError::IoError(e))?;
-            
+            write_record(&mut *writer, &id, desc, seq, qual, self.line_width)
+                .map_err(paraseq::ProcessError::IoError)?;
+
             let mut count = self.written.lock().unwrap();
             *count += 1;
         }
@@ -56,6 +79,26 @@ impl<R: Record> ParallelProcessor<R> for FastaFilterPy {
 ///     headers (list[str]): List of sequence IDs to filter
 ///     invert (bool): If True, keep sequences NOT in the headers list. Default: False
 ///     num_threads (int | None): Number of threads to use. Default: number of CPUs
+///     line_width (int): Wrap FASTA sequence lines at this many characters. 0 = single
+///         line (default). Ignored for FASTQ output, which is always written unwrapped.
+///     compression_level (int): Compression level (1-9) used when output_file's extension
+///         (.gz, .bz2, .zst, .xz) requests compression. Default: 6.
+///     preserve_order (bool): If True, output records in the same order as the input.
+///         paraseq doesn't expose a global per-record position to user processors, so
+///         this is implemented by forcing single-threaded processing rather than
+///         reordering parallel output. Default: False.
+///     min_len (int | None): Keep only records with sequence length >= this value.
+///     max_len (int | None): Keep only records with sequence length <= this value.
+///     min_gc (float | None): Keep only records with GC fraction (0.0-1.0) >= this value.
+///     max_gc (float | None): Keep only records with GC fraction (0.0-1.0) <= this value.
+///     kmers (list[str] | None): Probe sequences; a record is kept only if it shares a
+///         canonical k-mer with at least one probe.
+///     k (int): K-mer size used with `kmers`. Default: 21.
+///     match_mode (str): How `headers` patterns are interpreted: "exact" (default),
+///         "substring", "glob", or "regex".
+///
+/// All content predicates are ANDed together and with the header filter; headers may be
+/// left empty to filter purely on content.
 ///
 /// Returns:
 ///     dict: Dictionary with 'processed' and 'written' record counts
@@ -64,14 +107,21 @@ impl<R: Record> ParallelProcessor<R> for FastaFilterPy {
 ///     >>> import paraseq_filt
 ///     >>> result = paraseq_filt.filter_fasta_by_headers(
 ///     ...     "input.fasta",
-///     ...     "output.fasta",
+///     ...     "output.fasta.gz",
 ///     ...     ["seq1", "seq2", "seq3"],
 ///     ...     invert=False,
 ///     ...     num_threads=4
 ///     ... )
 ///     >>> print(f"Processed {result['processed']}, wrote {result['written']}")
 #[pyfunction]
-#[pyo3(signature = (input_file, output_file, headers, invert=false, num_threads=None))]
+#[pyo3(signature = (
+    input_file, output_file, headers, invert=false, num_threads=None, line_width=0,
+    compression_level=6, preserve_order=false, min_len=None, max_len=None, min_gc=None,
+    max_gc=None, kmers=None, k=21, match_mode="exact"
+))]
+// Mirrors the Python-facing keyword arguments one-for-one; splitting it into a
+// builder/options struct would just move the same arity into a constructor.
+#[allow(clippy::too_many_arguments)]
 fn filter_fasta_by_headers(
     py: Python<'_>,
     input_file: &str,
@@ -79,40 +129,72 @@ fn filter_fasta_by_headers(
     headers: Vec<String>,
     invert: bool,
     num_threads: Option<usize>,
+    line_width: usize,
+    compression_level: u8,
+    preserve_order: bool,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    min_gc: Option<f64>,
+    max_gc: Option<f64>,
+    kmers: Option<Vec<String>>,
+    k: usize,
+    match_mode: &str,
 ) -> PyResult<(u64, u64)> {
-    // Convert headers to HashSet
-    let headers_set: HashSet<String> = headers.into_iter().collect();
-    
+    let header_matcher = compile_header_matcher(headers, match_mode).map_err(PyRuntimeError::new_err)?;
+
+    if kmers.is_some() {
+        validate_k(k).map_err(PyRuntimeError::new_err)?;
+    }
+
+    let content = ContentFilter {
+        min_len,
+        max_len,
+        min_gc,
+        max_gc,
+        kmers: kmers.map(|probes| Arc::new(build_kmer_set(&probes, k))),
+        k,
+    };
+
     // Get number of threads
     let num_threads = num_threads.unwrap_or_else(num_cpus::get);
-    
-    // Open output file
+    if preserve_order && num_threads != 1 {
+        eprintln!("preserve_order=True: forcing single-threaded processing to preserve input order");
+    }
+    let num_threads = effective_num_threads(num_threads, preserve_order);
+
+    // Open output file, wrapping it in a compressing writer if the extension asks for it
     let output_path = Path::new(output_file);
-    let output_file = File::create(output_path)
-        .map_err(|e| PyRuntimeError::new_err(format!("Failed to create output file: {}", e)))?;
+    let output_file: Box<dyn Write + Send> = Box::new(
+        File::create(output_path)
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create output file: {}", e)))?,
+    );
+    let output_file = compressed_writer(output_file, output_path, compression_level)
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to set up output compression: {}", e)))?;
     let writer = BufWriter::new(output_file);
-    
+
     // Create processor
     let mut processor = FastaFilterPy {
-        headers: Arc::new(headers_set),
+        headers: Arc::new(header_matcher),
         writer: Arc::new(Mutex::new(writer)),
         invert,
+        line_width,
+        content,
         processed: Arc::new(Mutex::new(0)),
         written: Arc::new(Mutex::new(0)),
     };
-    
+
     // Copy input_file to owned String for use in closure
     let input_file = input_file.to_string();
-    
+
     // Release the GIL while processing to allow other Python threads to run
     // and to enable true parallelism in Rust
-    let result = py.allow_threads(|| {
+    let result = py.detach(|| {
         let reader = paraseq::fastx::Reader::from_path(&input_file)?;
         reader.process_parallel(&mut processor, num_threads)
     });
-    
+
     result.map_err(|e| PyRuntimeError::new_err(format!("Failed to process file: {:?}", e)))?;
-    
+
     let total_processed = *processor.processed.lock().unwrap();
     let total_written = *processor.written.lock().unwrap();
     
@@ -203,7 +285,7 @@ fn count_records(
     
     // Release the GIL while processing to allow other Python threads to run
     // and to enable true parallelism in Rust
-    let result = py.allow_threads(|| {
+    let result = py.detach(|| {
         let reader = paraseq::fastx::Reader::from_path(&input_file)?;
         reader.process_parallel(&mut counter, num_threads)
     });
@@ -212,10 +294,121 @@ fn count_records(
     
     let n_seqs = counter.n_seqs.load(Ordering::Relaxed);
     let n_bases = counter.n_bases.load(Ordering::Relaxed);
-    
+
     Ok((n_seqs, n_bases))
 }
 
+/// Compute summary statistics for a FASTA/FASTQ file
+///
+/// Args:
+///     input_file (str): Path to input FASTA/FASTQ file (supports .gz)
+///     num_threads (int | None): Number of threads to use. Default: number of CPUs
+///
+/// Returns:
+///     dict: min_len, max_len, mean_len, median_len, num_seqs, total_bases,
+///         gc_fraction, n50, l50, and (for FASTQ input) mean_qual and
+///         qual_histogram (a list indexed by Phred score).
+///
+/// Example:
+///     >>> import paraseq_filt
+///     >>> stats = paraseq_filt.compute_stats("input.fastq.gz")
+///     >>> print(f"N50={stats['n50']} mean_len={stats['mean_len']:.1f}")
+#[pyfunction]
+#[pyo3(signature = (input_file, num_threads=None))]
+fn compute_stats(py: Python<'_>, input_file: &str, num_threads: Option<usize>) -> PyResult<Py<PyAny>> {
+    use pyo3::types::PyDict;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Clone)]
+    struct StatsCollector {
+        lengths: Arc<Mutex<Vec<u64>>>,
+        total_bases: Arc<AtomicU64>,
+        total_gc: Arc<AtomicU64>,
+        qual_hist: Arc<Mutex<Vec<u64>>>,
+        has_qual: Arc<Mutex<bool>>,
+    }
+
+    impl<R: Record> ParallelProcessor<R> for StatsCollector {
+        fn process_record(&mut self, record: R) -> Result<(), paraseq::ProcessError> {
+            let seq_bytes = record.seq();
+            let len = seq_bytes.len() as u64;
+            let gc = seq_bytes
+                .iter()
+                .filter(|b| matches!(b, b'G' | b'g' | b'C' | b'c'))
+                .count() as u64;
+
+            self.lengths.lock().unwrap().push(len);
+            self.total_bases.fetch_add(len, Ordering::Relaxed);
+            self.total_gc.fetch_add(gc, Ordering::Relaxed);
+
+            if let Some(qual) = record.qual() {
+                *self.has_qual.lock().unwrap() = true;
+                let mut hist = self.qual_hist.lock().unwrap();
+                for &q in qual {
+                    let score = q.saturating_sub(33) as usize;
+                    if score < hist.len() {
+                        hist[score] += 1;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    let num_threads = num_threads.unwrap_or_else(num_cpus::get);
+
+    let mut collector = StatsCollector {
+        lengths: Arc::new(Mutex::new(Vec::new())),
+        total_bases: Arc::new(AtomicU64::new(0)),
+        total_gc: Arc::new(AtomicU64::new(0)),
+        qual_hist: Arc::new(Mutex::new(vec![0u64; QUAL_HISTOGRAM_BINS])),
+        has_qual: Arc::new(Mutex::new(false)),
+    };
+
+    let input_file = input_file.to_string();
+
+    let result = py.detach(|| {
+        let reader = paraseq::fastx::Reader::from_path(&input_file)?;
+        reader.process_parallel(&mut collector, num_threads)
+    });
+
+    result.map_err(|e| PyRuntimeError::new_err(format!("Failed to process file: {:?}", e)))?;
+
+    let lengths = Arc::try_unwrap(collector.lengths).unwrap().into_inner().unwrap();
+    let total_bases = collector.total_bases.load(Ordering::Relaxed);
+    let total_gc = collector.total_gc.load(Ordering::Relaxed);
+    let num_seqs = lengths.len() as u64;
+
+    let (n50, l50, sorted_lengths) = n50_l50(lengths, total_bases);
+    let (min_len, max_len, mean_len, median_len) = length_stats(&sorted_lengths, total_bases, num_seqs);
+
+    let gc_fraction = if total_bases > 0 {
+        total_gc as f64 / total_bases as f64
+    } else {
+        0.0
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item("num_seqs", num_seqs)?;
+    dict.set_item("total_bases", total_bases)?;
+    dict.set_item("min_len", min_len)?;
+    dict.set_item("max_len", max_len)?;
+    dict.set_item("mean_len", mean_len)?;
+    dict.set_item("median_len", median_len)?;
+    dict.set_item("gc_fraction", gc_fraction)?;
+    dict.set_item("n50", n50)?;
+    dict.set_item("l50", l50)?;
+
+    if *collector.has_qual.lock().unwrap() {
+        let qual_hist = Arc::try_unwrap(collector.qual_hist).unwrap().into_inner().unwrap();
+        dict.set_item("mean_qual", mean_qual(&qual_hist))?;
+        dict.set_item("qual_histogram", qual_hist)?;
+    }
+
+    Ok(dict.into_any().unbind())
+}
+
 /// Parse FASTA/FASTQ records and yield (id, sequence, quality) tuples
 ///
 /// Args:
@@ -239,10 +432,10 @@ fn count_records(
 ///     ...     print(f"+")
 ///     ...     print(qual)
 #[pyfunction]
-fn parse_records(py: Python<'_>, input_file: &str) -> PyResult<Vec<(String, String, Option<String>)>> {
+fn parse_records(py: Python<'_>, input_file: &str) -> PyResult<Vec<RecordTuple>> {
     #[derive(Clone)]
     struct RecordCollector {
-        records: Arc<Mutex<Vec<(String, String, Option<String>)>>>,
+        records: Arc<Mutex<Vec<RecordTuple>>>,
     }
     
     impl<R: Record> ParallelProcessor<R> for RecordCollector {
@@ -272,7 +465,7 @@ fn parse_records(py: Python<'_>, input_file: &str) -> PyResult<Vec<(String, Stri
     let input_file = input_file.to_string();
     
     // Release the GIL while processing to allow other Python threads to run
-    let result = py.allow_threads(|| {
+    let result = py.detach(|| {
         let reader = paraseq::fastx::Reader::from_path(&input_file)?;
         // Use single thread to preserve order
         reader.process_parallel(&mut collector, 1)
@@ -288,12 +481,131 @@ fn parse_records(py: Python<'_>, input_file: &str) -> PyResult<Vec<(String, Stri
     Ok(records)
 }
 
+type StreamItem = PyResult<RecordTuple>;
+
+#[derive(Clone)]
+struct StreamingProcessor {
+    sender: SyncSender<StreamItem>,
+}
+
+impl<R: Record> ParallelProcessor<R> for StreamingProcessor {
+    fn process_record(&mut self, record: R) -> Result<(), paraseq::ProcessError> {
+        let id = record.id_str().to_string();
+        let seq_bytes = record.seq();
+        let seq = std::str::from_utf8(&seq_bytes)
+            .unwrap_or_default()
+            .to_string();
+        let qual = record.qual().map(|q| {
+            std::str::from_utf8(q)
+                .unwrap_or_default()
+                .to_string()
+        });
+
+        // If the receiver (the Python-side iterator) was dropped, there's nothing
+        // left to stream to; return an error so paraseq stops reading. Nobody is
+        // listening any more, so the worker's attempt to forward it goes nowhere.
+        if self.sender.send(Ok((id, seq, qual))).is_err() {
+            return Err(paraseq::ProcessError::Process(Box::new(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "record iterator dropped",
+            ))));
+        }
+
+        Ok(())
+    }
+}
+
+/// A lazy, record-at-a-time iterator over a FASTA/FASTQ file.
+///
+/// Reads happen on a background thread and are handed to Python one record at a
+/// time through a bounded channel, so memory use stays constant regardless of
+/// file size. Construct one with `paraseq_filt.open(...)`.
+#[pyclass]
+struct RecordReader {
+    receiver: Mutex<Receiver<StreamItem>>,
+    worker: Mutex<Option<std::thread::JoinHandle<()>>>,
+}
+
+impl RecordReader {
+    fn new(input_file: &str) -> PyResult<Self> {
+        // Bound the channel so the worker can't race arbitrarily far ahead of the
+        // consumer and re-introduce the unbounded memory growth this replaces.
+        let (sender, receiver) = sync_channel::<StreamItem>(1024);
+        let input_file = input_file.to_string();
+
+        let worker = std::thread::spawn(move || {
+            let outcome = (|| -> PyResult<()> {
+                let reader = paraseq::fastx::Reader::from_path(&input_file)
+                    .map_err(|e| PyRuntimeError::new_err(format!("Failed to open input file: {}", e)))?;
+                let mut processor = StreamingProcessor { sender: sender.clone() };
+                // Single thread to preserve file order in the yielded stream
+                reader
+                    .process_parallel(&mut processor, 1)
+                    .map_err(|e| PyRuntimeError::new_err(format!("Failed to process file: {:?}", e)))?;
+                Ok(())
+            })();
+
+            if let Err(e) = outcome {
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        Ok(RecordReader {
+            receiver: Mutex::new(receiver),
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+}
+
+#[pymethods]
+impl RecordReader {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<RecordTuple>> {
+        let next = py.detach(|| self.receiver.lock().unwrap().recv());
+        match next {
+            Ok(item) => item.map(Some),
+            Err(_) => {
+                // Channel closed: the worker is done, so join it and signal StopIteration.
+                if let Some(worker) = self.worker.lock().unwrap().take() {
+                    let _ = worker.join();
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Open a FASTA/FASTQ file for lazy, record-at-a-time streaming
+///
+/// Args:
+///     input_file (str): Path to input FASTA/FASTQ file (supports .gz)
+///
+/// Returns:
+///     RecordReader: Iterator yielding (id, sequence, quality) tuples. For FASTA
+///         files, quality is None.
+///
+/// Example:
+///     >>> import paraseq_filt
+///     >>> for seq_id, sequence, qual in paraseq_filt.open("reads.fastq.gz"):
+///     ...     print(f"@{seq_id}")
+///     ...     print(sequence)
+#[pyfunction]
+fn open(input_file: &str) -> PyResult<RecordReader> {
+    RecordReader::new(input_file)
+}
+
 /// Fast parallel FASTA/FASTQ filtering using Rust and paraseq
 #[pymodule]
 fn paraseq_filt(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(filter_fasta_by_headers, m)?)?;
     m.add_function(wrap_pyfunction!(load_headers_from_file, m)?)?;
     m.add_function(wrap_pyfunction!(count_records, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_stats, m)?)?;
     m.add_function(wrap_pyfunction!(parse_records, m)?)?;
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    m.add_class::<RecordReader>()?;
     Ok(())
 }
@@ -1,12 +1,20 @@
 use anyhow::{Context, Result};
 use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
 use paraseq::prelude::*;
-use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+#[path = "common.rs"]
+mod common;
+use common::{
+    build_kmer_set, compile_header_matcher, compressed_writer, effective_num_threads, length_stats,
+    mean_qual, n50_l50, split_id_desc, validate_k, write_record, ContentFilter, HeaderMatcher,
+    QUAL_HISTOGRAM_BINS,
+};
+
 #[derive(Parser, Debug)]
 #[command(name = "paraseq_filt")]
 #[command(about = "Fast parallel FASTA/FASTQ filtering tool", long_about = None)]
@@ -23,6 +31,10 @@ struct Args {
     #[arg(short = 'H', long)]
     headers: Option<String>,
 
+    /// How to interpret --headers patterns: exact, substring, glob, or regex
+    #[arg(long, default_value = "exact")]
+    mode: String,
+
     /// Invert match - keep records NOT in the header list
     #[arg(short = 'v', long)]
     invert: bool,
@@ -34,44 +46,109 @@ struct Args {
     /// Count mode - just count reads and bases without filtering
     #[arg(short, long)]
     count: bool,
+
+    /// Wrap FASTA sequence lines at this many characters (0 = single line).
+    /// Ignored for FASTQ output, which is always written unwrapped.
+    #[arg(short = 'w', long, default_value_t = 0)]
+    line_width: usize,
+
+    /// Compression level (1-9) used when --output's extension (.gz, .bz2, .zst, .xz)
+    /// requests compression
+    #[arg(long, default_value_t = 6)]
+    compression_level: u8,
+
+    /// Preserve input order in the output. paraseq doesn't expose a global
+    /// per-record position to user processors, so this forces single-threaded
+    /// processing rather than reordering parallel output after the fact.
+    #[arg(long)]
+    ordered: bool,
+
+    /// Keep only records with sequence length >= this value
+    #[arg(long)]
+    min_len: Option<usize>,
+
+    /// Keep only records with sequence length <= this value
+    #[arg(long)]
+    max_len: Option<usize>,
+
+    /// Keep only records with GC fraction (0.0-1.0) >= this value
+    #[arg(long)]
+    min_gc: Option<f64>,
+
+    /// Keep only records with GC fraction (0.0-1.0) <= this value
+    #[arg(long)]
+    max_gc: Option<f64>,
+
+    /// Probe sequences to match by k-mer presence (comma-separated or path to a
+    /// file with one sequence per line). A record is kept if it shares a
+    /// canonical k-mer with at least one probe.
+    #[arg(long)]
+    kmers: Option<String>,
+
+    /// K-mer size used with --kmers
+    #[arg(long, default_value_t = 21)]
+    k: usize,
+
+    /// Stats mode - compute length/GC/N50 and quality summary statistics without filtering
+    #[arg(long)]
+    stats: bool,
+
+    /// Show a live progress bar (records/sec, elapsed) instead of the every-100k-records log line
+    #[arg(long)]
+    progress: bool,
 }
 
 #[derive(Clone)]
 struct FastaFilter {
-    headers: Arc<HashSet<String>>,
+    headers: Arc<HeaderMatcher>,
     writer: Arc<Mutex<BufWriter<Box<dyn Write + Send>>>>,
     invert: bool,
+    line_width: usize,
+    content: ContentFilter,
     processed: Arc<Mutex<u64>>,
     written: Arc<Mutex<u64>>,
+    progress: Option<Arc<ProgressBar>>,
 }
 
 impl<R: Record> ParallelProcessor<R> for FastaFilter {
     fn process_record(&mut self, record: R) -> Result<(), paraseq::ProcessError> {
-        let id = record.id_str().to_string();
+        let (id_bytes, desc) = split_id_desc(record.id());
+        let id = String::from_utf8_lossy(id_bytes).into_owned();
         let seq_bytes = record.seq();
         let seq = std::str::from_utf8(&seq_bytes)
             .map_err(|e| paraseq::ProcessError::Process(Box::new(e)))?;
+        let qual_bytes = record.qual();
+        let qual = qual_bytes
+            .map(|q| std::str::from_utf8(q))
+            .transpose()
+            .map_err(|e| paraseq::ProcessError::Process(Box::new(e)))?;
 
         // Update processed count
         {
             let mut count = self.processed.lock().unwrap();
             *count += 1;
-            if *count % 100_000 == 0 {
+            if let Some(progress) = &self.progress {
+                progress.set_position(*count);
+            } else if (*count).is_multiple_of(100_000) {
                 eprintln!("Processed {} records", *count);
             }
         }
 
-        let should_write = if self.invert {
-            !self.headers.contains(&id)
+        let header_match = if self.headers.is_empty() {
+            true
+        } else if self.invert {
+            !self.headers.matches(&id)
         } else {
-            self.headers.contains(&id)
+            self.headers.matches(&id)
         };
 
+        let should_write = header_match && self.content.matches(&seq_bytes);
+
         if should_write {
             let mut writer = self.writer.lock().unwrap();
-            writeln!(writer, ">{}\n{}", id, seq)
-                .map_err(|e| paraseq::ProcessError::IoError(e))?;
-            
+            write_record(&mut *writer, &id, desc, seq, qual, self.line_width)
+                .map_err(paraseq::ProcessError::IoError)?;
+
             let mut count = self.written.lock().unwrap();
             *count += 1;
         }
@@ -85,6 +162,10 @@ fn main() -> Result<()> {
 
     // Configure threads
     let num_threads = args.threads.unwrap_or_else(num_cpus::get);
+    if args.ordered && num_threads != 1 {
+        eprintln!("--ordered requested: forcing single-threaded processing to preserve input order");
+    }
+    let num_threads = effective_num_threads(num_threads, args.ordered);
     eprintln!("Using {} threads", num_threads);
 
     // Count mode - just count reads and bases
@@ -92,45 +173,92 @@ fn main() -> Result<()> {
         return count_mode(&args.input, num_threads);
     }
 
-    // Filter mode - require headers and output
-    let headers_input = args.headers
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("--headers is required for filtering mode"))?;
-    
+    // Stats mode - compute summary statistics without filtering
+    if args.stats {
+        return stats_mode(&args.input, num_threads);
+    }
+
+    // Filter mode - require output; headers are optional when filtering purely by content
     let output_path = args.output
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("--output is required for filtering mode"))?;
 
-    // Load headers into HashSet for O(1) lookup
-    let headers = load_headers(headers_input)?;
+    // Load header patterns and compile them per --mode (exact/substring/glob/regex)
+    let headers = match &args.headers {
+        Some(headers_input) => load_list(headers_input)?,
+        None => Vec::new(),
+    };
     eprintln!("Loaded {} headers", headers.len());
+    let header_matcher = compile_header_matcher(headers, &args.mode)
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    // Load k-mer probes, if any, into a canonical k-mer set
+    let kmers = match &args.kmers {
+        Some(kmers_input) => {
+            validate_k(args.k).map_err(|e| anyhow::anyhow!(e))?;
+            Some(Arc::new(build_kmer_set(&load_list(kmers_input)?, args.k)))
+        }
+        None => None,
+    };
 
-    // Open output file or stdout
+    let content = ContentFilter {
+        min_len: args.min_len,
+        max_len: args.max_len,
+        min_gc: args.min_gc,
+        max_gc: args.max_gc,
+        kmers,
+        k: args.k,
+    };
+
+    let num_threads = effective_num_threads(num_threads, args.ordered);
+
+    // Open output file or stdout, wrapping it in a compressing writer if the extension asks for it
     let output_file: Box<dyn Write + Send> = if output_path.to_str() == Some("-") || output_path.to_str() == Some("/dev/stdout") {
         Box::new(std::io::stdout())
     } else {
         Box::new(File::create(output_path)
             .with_context(|| format!("Failed to create output file: {:?}", output_path))?)
     };
+    let output_file = compressed_writer(output_file, output_path, args.compression_level)
+        .with_context(|| format!("Failed to set up output compression for: {:?}", output_path))?;
     let writer = BufWriter::new(output_file);
 
+    // Set up an optional live progress bar, replacing the every-100k-records log line
+    let progress = if args.progress {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} {elapsed_precise} {pos} records ({per_sec})")
+                .unwrap(),
+        );
+        Some(Arc::new(bar))
+    } else {
+        None
+    };
+
     // Create processor
     let mut processor = FastaFilter {
-        headers: Arc::new(headers),
+        headers: Arc::new(header_matcher),
         writer: Arc::new(Mutex::new(writer)),
         invert: args.invert,
+        line_width: args.line_width,
+        content,
         processed: Arc::new(Mutex::new(0)),
         written: Arc::new(Mutex::new(0)),
+        progress: progress.clone(),
     };
 
     // Process FASTA file
     let reader = paraseq::fastx::Reader::from_path(&args.input)
         .map_err(|e| anyhow::anyhow!("Failed to open input file: {}", e))?;
-    
+
     reader
         .process_parallel(&mut processor, num_threads)
         .map_err(|e| anyhow::anyhow!("Failed to process file: {:?}", e))?;
 
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+
     let total_processed = *processor.processed.lock().unwrap();
     let total_written = *processor.written.lock().unwrap();
 
@@ -188,33 +316,230 @@ fn count_mode(input_path: &PathBuf, num_threads: usize) -> Result<()> {
     Ok(())
 }
 
-fn load_headers(headers_input: &str) -> Result<HashSet<String>> {
-    let mut headers = HashSet::new();
+#[derive(Clone)]
+struct StatsCollector {
+    lengths: Arc<Mutex<Vec<u64>>>,
+    total_bases: Arc<Mutex<u64>>,
+    total_gc: Arc<Mutex<u64>>,
+    qual_hist: Arc<Mutex<Vec<u64>>>,
+    has_qual: Arc<Mutex<bool>>,
+}
+
+impl<R: Record> ParallelProcessor<R> for StatsCollector {
+    fn process_record(&mut self, record: R) -> Result<(), paraseq::ProcessError> {
+        let seq_bytes = record.seq();
+        let len = seq_bytes.len() as u64;
+        let gc = seq_bytes
+            .iter()
+            .filter(|b| matches!(b, b'G' | b'g' | b'C' | b'c'))
+            .count() as u64;
+
+        self.lengths.lock().unwrap().push(len);
+        *self.total_bases.lock().unwrap() += len;
+        *self.total_gc.lock().unwrap() += gc;
+
+        if let Some(qual) = record.qual() {
+            *self.has_qual.lock().unwrap() = true;
+            let mut hist = self.qual_hist.lock().unwrap();
+            for &q in qual {
+                let score = q.saturating_sub(33) as usize;
+                if score < hist.len() {
+                    hist[score] += 1;
+                }
+            }
+        }
 
-    // Check if it's a file path
-    let path = std::path::Path::new(headers_input);
+        Ok(())
+    }
+}
+
+fn stats_mode(input_path: &PathBuf, num_threads: usize) -> Result<()> {
+    let mut collector = StatsCollector {
+        lengths: Arc::new(Mutex::new(Vec::new())),
+        total_bases: Arc::new(Mutex::new(0)),
+        total_gc: Arc::new(Mutex::new(0)),
+        qual_hist: Arc::new(Mutex::new(vec![0u64; QUAL_HISTOGRAM_BINS])),
+        has_qual: Arc::new(Mutex::new(false)),
+    };
+
+    let reader = paraseq::fastx::Reader::from_path(input_path)
+        .map_err(|e| anyhow::anyhow!("Failed to open input file: {}", e))?;
+
+    reader
+        .process_parallel(&mut collector, num_threads)
+        .map_err(|e| anyhow::anyhow!("Failed to process file: {:?}", e))?;
+
+    let lengths = Arc::try_unwrap(collector.lengths).unwrap().into_inner().unwrap();
+    let total_bases = *collector.total_bases.lock().unwrap();
+    let total_gc = *collector.total_gc.lock().unwrap();
+    let num_seqs = lengths.len() as u64;
+
+    let (n50, l50, sorted_lengths) = n50_l50(lengths, total_bases);
+    let (min_len, max_len, mean_len, median_len) = length_stats(&sorted_lengths, total_bases, num_seqs);
+
+    let gc_fraction = if total_bases > 0 {
+        total_gc as f64 / total_bases as f64
+    } else {
+        0.0
+    };
+
+    println!("num_seqs\t{}", num_seqs);
+    println!("total_bases\t{}", total_bases);
+    println!("min_len\t{}", min_len);
+    println!("max_len\t{}", max_len);
+    println!("mean_len\t{:.2}", mean_len);
+    println!("median_len\t{:.1}", median_len);
+    println!("gc_fraction\t{:.4}", gc_fraction);
+    println!("n50\t{}", n50);
+    println!("l50\t{}", l50);
+
+    if *collector.has_qual.lock().unwrap() {
+        let qual_hist = Arc::try_unwrap(collector.qual_hist).unwrap().into_inner().unwrap();
+        println!("mean_qual\t{:.2}", mean_qual(&qual_hist));
+    }
+
+    Ok(())
+}
+
+/// Parse a comma-separated list, or (if `input` names an existing file) read it as
+/// one entry per line. Shared by --headers and --kmers.
+fn load_list(input: &str) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+
+    let path = std::path::Path::new(input);
     if path.exists() {
-        // Read from file
         let file = File::open(path)
-            .with_context(|| format!("Failed to open headers file: {}", headers_input))?;
+            .with_context(|| format!("Failed to open file: {}", input))?;
         let reader = BufReader::new(file);
 
         for line in reader.lines() {
             let line = line?;
-            let header = line.trim();
-            if !header.is_empty() {
-                headers.insert(header.to_string());
+            let item = line.trim();
+            if !item.is_empty() {
+                items.push(item.to_string());
             }
         }
     } else {
-        // Treat as comma-separated list
-        for header in headers_input.split(',') {
-            let header = header.trim();
-            if !header.is_empty() {
-                headers.insert(header.to_string());
+        for item in input.split(',') {
+            let item = item.trim();
+            if !item.is_empty() {
+                items.push(item.to_string());
             }
         }
     }
 
-    Ok(headers)
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp_fasta(ids: &[String]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "paraseq_filt_order_in_{}_{}.fasta",
+            std::process::id(),
+            ids.len()
+        ));
+        let mut file = File::create(&path).unwrap();
+        for id in ids {
+            writeln!(file, ">{}", id).unwrap();
+            writeln!(file, "ACGT").unwrap();
+        }
+        path
+    }
+
+    /// Regression test for the original `record.index()`-based reorder buffer:
+    /// paraseq exposes no global per-record position to user processors, so
+    /// `--ordered` forces single-threaded processing instead. This drives the real
+    /// `FastaFilter` processor over enough records to span many parallel batches
+    /// at the default batch size, requests several threads, and asserts the
+    /// written order still matches the input order exactly.
+    #[test]
+    fn ordered_mode_preserves_input_order_across_batches() {
+        let ids: Vec<String> = (0..2_000).map(|i| format!("seq{i}")).collect();
+        let input_path = write_temp_fasta(&ids);
+        let output_path = std::env::temp_dir().join(format!(
+            "paraseq_filt_order_out_{}.fasta",
+            std::process::id()
+        ));
+
+        let output_file: Box<dyn Write + Send> = Box::new(File::create(&output_path).unwrap());
+        let writer = BufWriter::new(output_file);
+
+        let mut processor = FastaFilter {
+            headers: Arc::new(compile_header_matcher(Vec::new(), "exact").unwrap()),
+            writer: Arc::new(Mutex::new(writer)),
+            invert: false,
+            line_width: 0,
+            content: ContentFilter::default(),
+            processed: Arc::new(Mutex::new(0)),
+            written: Arc::new(Mutex::new(0)),
+            progress: None,
+        };
+
+        let reader = paraseq::fastx::Reader::from_path(&input_path).unwrap();
+        let num_threads = effective_num_threads(8, true);
+        assert_eq!(num_threads, 1, "preserve_order must force single-threaded processing");
+        reader.process_parallel(&mut processor, num_threads).unwrap();
+        drop(processor);
+
+        let output = BufReader::new(File::open(&output_path).unwrap());
+        let written_ids: Vec<String> = output
+            .lines()
+            .map(|line| line.unwrap())
+            .filter(|line| line.starts_with('>'))
+            .map(|line| line[1..].to_string())
+            .collect();
+
+        assert_eq!(written_ids, ids);
+
+        let _ = std::fs::remove_file(&input_path);
+        let _ = std::fs::remove_file(&output_path);
+    }
+
+    /// Regression test: an empty `--headers` list must bypass header matching
+    /// entirely, even with `--invert` set, rather than `!true` (empty list always
+    /// matches) turning into `false` and silently dropping every record that
+    /// otherwise satisfies the content filter.
+    #[test]
+    fn empty_headers_with_invert_and_content_filter_keeps_matching_records() {
+        let ids = vec!["seq0".to_string()];
+        let input_path = write_temp_fasta(&ids);
+        let output_path = std::env::temp_dir().join(format!(
+            "paraseq_filt_invert_out_{}.fasta",
+            std::process::id()
+        ));
+
+        let output_file: Box<dyn Write + Send> = Box::new(File::create(&output_path).unwrap());
+        let writer = BufWriter::new(output_file);
+
+        let written = Arc::new(Mutex::new(0));
+        let mut processor = FastaFilter {
+            headers: Arc::new(compile_header_matcher(Vec::new(), "exact").unwrap()),
+            writer: Arc::new(Mutex::new(writer)),
+            invert: true,
+            line_width: 0,
+            content: ContentFilter {
+                min_len: Some(4),
+                ..ContentFilter::default()
+            },
+            processed: Arc::new(Mutex::new(0)),
+            written: written.clone(),
+            progress: None,
+        };
+
+        let reader = paraseq::fastx::Reader::from_path(&input_path).unwrap();
+        reader.process_parallel(&mut processor, 1).unwrap();
+        drop(processor);
+
+        assert_eq!(
+            *written.lock().unwrap(),
+            1,
+            "record matching the content filter should survive invert with no headers"
+        );
+
+        let _ = std::fs::remove_file(&input_path);
+        let _ = std::fs::remove_file(&output_path);
+    }
 }